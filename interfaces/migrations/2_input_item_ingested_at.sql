@@ -0,0 +1,8 @@
+-- When each input item was ingested, for time-windowed lookups
+-- SQLite can't add a column defaulting to CURRENT_TIMESTAMP, so ingest sets it
+-- Items ingested before this column existed stay NULL: their age is unknown,
+-- so time-windowed lookups leave them out rather than treating them as new
+ALTER TABLE input_items ADD COLUMN ingested_at TIMESTAMP;
+
+-- Supports looking up an item's recent neighbors from the same live source
+CREATE INDEX input_items_live_source_uri_ingested_at ON input_items (live_source_uri, ingested_at);
@@ -1,3 +1,7 @@
+use std::collections::HashSet;
+
+use crate::defs::ContextProvider;
+use crate::defs::DigestCitation;
 use crate::defs::DigestModel;
 use crate::defs::DigestModelMemory;
 use crate::defs::DigestModelSpec;
@@ -5,7 +9,10 @@ use crate::defs::DigestOutput;
 use crate::defs::DigestPreferences;
 use crate::defs::DigestSelectedItem;
 use crate::defs::InputItemReference;
+use crate::defs::InvalidCitationPolicy;
+use crate::defs::escape_citations;
 use crate::defs::InputItem;
+use crate::empty::EmptyContextProvider;
 
 struct PonderedPreferences {
     pub look_out_for: String,
@@ -25,12 +32,38 @@ struct FocusedSummary {
     pub references: Vec<InputItemReference>,
 }
 
+/// Label the first line as the title (`a`), unless it's empty.
+fn title_reference(text: &str) -> Option<InputItemReference> {
+    let title_end = text.find('\n').unwrap_or(text.len());
+    (title_end > 0).then(|| InputItemReference { text_start_index: 0, text_end_index: title_end, label: Some("a".to_owned()) })
+}
+
+/// Label the first line as the title (`a`) and, when there's more text after
+/// it, the first sentence of that as supporting it (`b`). Empty spans get no
+/// reference.
+fn title_and_first_sentence_references(text: &str) -> Vec<InputItemReference> {
+    let title_end = text.find('\n').unwrap_or(text.len());
+    let mut references = title_reference(text).into_iter().collect::<Vec<InputItemReference>>();
+    let after_title = text.get(title_end + 1..).unwrap_or("");
+    let sentence = after_title.trim_start();
+    if !sentence.is_empty() {
+        let sentence_start = text.len() - sentence.len();
+        let sentence_len = sentence.char_indices()
+            .filter(|(_, c)| matches!(c, '.' | '!' | '?'))
+            .map(|(index, c)| index + c.len_utf8())
+            .find(|end| sentence[*end..].chars().next().is_none_or(char::is_whitespace))
+            .unwrap_or(sentence.len());
+        references.push(InputItemReference { text_start_index: sentence_start, text_end_index: sentence_start + sentence_len, label: Some("b".to_owned()) });
+    }
+    references
+}
+
 async fn ponder_relevance_and_summarize(spec: &DigestModelSpec, pondered_preferences: &PonderedPreferences, input_item: &InputItem) -> FocusedSummary {
     _ = spec;
     _ = pondered_preferences.look_out_for;
     FocusedSummary {
         summary_text: input_item.text.clone(),
-        references: vec![InputItemReference { text_start_index: 0, text_end_index: input_item.text.len() }],
+        references: title_and_first_sentence_references(&input_item.text),
     }
 }
 
@@ -40,10 +73,90 @@ async fn select_best(spec: &DigestModelSpec, pondered_preferences: &PonderedPref
     (0..focused_summaries.len()).collect()
 }
 
-async fn compose_digest(spec: &DigestModelSpec, pondered_preferences: &PonderedPreferences, best_summaries: &[FocusedSummary]) -> String {
+/// Write out a summary with a citation right after each labeled span it
+/// references. Spans index into the summary text, which here is the item text,
+/// so anything in it that reads as a citation is escaped. Citations that can't
+/// be written, including any to an empty span, are left out and added to
+/// `invalid_citations`.
+fn cite_summary(summary: &FocusedSummary, selected_item_index: usize, invalid_citations: &mut Vec<DigestCitation>) -> String {
+    let mut labeled_references = summary.references.iter().filter_map(|reference| Some((reference.text_start_index, reference.text_end_index, reference.label.as_ref()?))).collect::<Vec<(usize, usize, &String)>>();
+    labeled_references.sort_by_key(|(_, text_end_index, _)| *text_end_index);
+    let mut text = String::with_capacity(summary.summary_text.len());
+    let mut copied_up_to = 0;
+    for (text_start_index, text_end_index, label) in labeled_references {
+        let citation = DigestCitation { selected_item_index, label: label.clone() };
+        let Some(marker) = citation.marker().filter(|_| text_start_index < text_end_index && summary.summary_text.is_char_boundary(text_end_index)) else {
+            invalid_citations.push(citation);
+            continue;
+        };
+        text.push_str(&escape_citations(&summary.summary_text[copied_up_to..text_end_index]));
+        text.push(' ');
+        text.push_str(&marker);
+        copied_up_to = text_end_index;
+    }
+    text.push_str(&escape_citations(&summary.summary_text[copied_up_to..]));
+    text
+}
+
+/// Most related items to pull in as context for a single selected item.
+const MAX_CONTEXT_ITEMS_PER_SELECTION: usize = 2;
+/// Most context items to pull in across the whole digest.
+const MAX_CONTEXT_ITEMS: usize = 8;
+
+/// A related item pulled in to support one of the selected items.
+struct ContextItem {
+    /// Index into the selected items of the one this supports.
+    supports: usize,
+    input_item: InputItem,
+    /// What the digest cites the item by.
+    title: InputItemReference,
+}
+
+async fn gather_context<C: ContextProvider>(spec: &DigestModelSpec, pondered_preferences: &PonderedPreferences, selected_input_items: &[&InputItem]) -> Vec<ContextItem> {
+    _ = spec;
+    _ = pondered_preferences.look_out_for;
+    let related_items_per_selection = futures::future::join_all(selected_input_items.iter().map(|input_item| C::related_items(input_item, MAX_CONTEXT_ITEMS_PER_SELECTION))).await;
+    let mut seen_uris = selected_input_items.iter().map(|input_item| input_item.uri.clone()).collect::<HashSet<String>>();
+    let mut context_items = vec![];
+    for (supports, related_items) in related_items_per_selection.into_iter().enumerate() {
+        for related_item in related_items {
+            if context_items.len() >= MAX_CONTEXT_ITEMS {
+                return context_items;
+            }
+            // Without a title there'd be nothing to cite it by.
+            let Some(title) = title_reference(&related_item.text) else {
+                continue;
+            };
+            if !seen_uris.insert(related_item.uri.clone()) {
+                continue;
+            }
+            context_items.push(ContextItem { supports, input_item: related_item, title });
+        }
+    }
+    context_items
+}
+
+async fn compose_digest(spec: &DigestModelSpec, pondered_preferences: &PonderedPreferences, best_summaries: &[FocusedSummary], context_items: &[ContextItem], invalid_citation_policy: InvalidCitationPolicy) -> Result<String, Vec<DigestCitation>> {
     _ = spec;
     _ = pondered_preferences.look_out_for;
-    best_summaries.iter().map(|summary| summary.summary_text.clone()).collect::<Vec<String>>().join("\n")
+    let mut invalid_citations = vec![];
+    // Citation numbers follow the order things end up in selected_items: the
+    // summaries, then the context items.
+    let text = best_summaries.iter().enumerate().map(|(index, summary)| {
+        let mut lines = vec![cite_summary(summary, index, &mut invalid_citations)];
+        for (context_index, context_item) in context_items.iter().enumerate().filter(|(_, context_item)| context_item.supports == index) {
+            let title_summary = FocusedSummary {
+                summary_text: context_item.input_item.text[..context_item.title.text_end_index].to_owned(),
+                references: vec![context_item.title.clone()],
+            };
+            lines.push(format!("Related: {}", cite_summary(&title_summary, best_summaries.len() + context_index, &mut invalid_citations)));
+        }
+        lines.join("\n")
+    }).collect::<Vec<String>>().join("\n");
+    match invalid_citation_policy {
+        InvalidCitationPolicy::Fail if !invalid_citations.is_empty() => Err(invalid_citations),
+        _ => Ok(text),
+    }
 }
 
 async fn reflect(spec: &DigestModelSpec, memory: &DigestModelMemory, preferences: &DigestPreferences, input_items: &[InputItem], self_output: &DigestOutput, opponent_output: &DigestOutput, win: bool) -> DigestModelMemory {
@@ -61,18 +174,38 @@ async fn reflect(spec: &DigestModelSpec, memory: &DigestModelMemory, preferences
 
 pub struct BaselineDigestModel;
 
-impl DigestModel for BaselineDigestModel {
-
-    async fn digest(spec: &DigestModelSpec, memory: &DigestModelMemory, preferences: &DigestPreferences, input_items: &[InputItem]) -> DigestOutput {
+impl BaselineDigestModel {
+    /// Like [`DigestModel::digest`], which strips citations it can't emit, but
+    /// lets the caller fail closed instead.
+    pub async fn digest_with_citation_policy<C: ContextProvider>(spec: &DigestModelSpec, memory: &DigestModelMemory, preferences: &DigestPreferences, input_items: &[InputItem], invalid_citation_policy: InvalidCitationPolicy) -> Result<DigestOutput, Vec<DigestCitation>> {
         let pondered_preferences = ponder_preferences(spec, memory, preferences).await;
         let focused_summaries = futures::future::join_all(input_items.iter().map(|input_item| ponder_relevance_and_summarize(spec, &pondered_preferences, input_item))).await;
         let best_summary_indices = select_best(spec, &pondered_preferences, &focused_summaries).await;
         let best_summaries = best_summary_indices.iter().map(|index| focused_summaries[*index].clone()).collect::<Vec<FocusedSummary>>();
-        let digest_text = compose_digest(spec, &pondered_preferences, &best_summaries).await;
-        DigestOutput {
-            selected_items: best_summary_indices.iter().map(|index| DigestSelectedItem { input_item_uri: input_items[*index].uri.clone(), references: focused_summaries[*index].references.clone() }).collect::<Vec<DigestSelectedItem>>(),
+        let best_input_items = best_summary_indices.iter().map(|index| &input_items[*index]).collect::<Vec<&InputItem>>();
+        let context_items = gather_context::<C>(spec, &pondered_preferences, &best_input_items).await;
+        let digest_text = compose_digest(spec, &pondered_preferences, &best_summaries, &context_items, invalid_citation_policy).await?;
+        let mut selected_items = best_summary_indices.iter().map(|index| DigestSelectedItem { input_item_uri: input_items[*index].uri.clone(), references: focused_summaries[*index].references.clone(), context_for: None }).collect::<Vec<DigestSelectedItem>>();
+        selected_items.extend(context_items.into_iter().map(|context_item| DigestSelectedItem {
+            references: vec![context_item.title],
+            context_for: Some(best_input_items[context_item.supports].uri.clone()),
+            input_item_uri: context_item.input_item.uri,
+        }));
+        Ok(DigestOutput {
+            selected_items,
             text: digest_text,
-        }
+        })
+    }
+}
+
+impl DigestModel for BaselineDigestModel {
+
+    async fn digest(spec: &DigestModelSpec, memory: &DigestModelMemory, preferences: &DigestPreferences, input_items: &[InputItem]) -> DigestOutput {
+        Self::digest_with_context::<EmptyContextProvider>(spec, memory, preferences, input_items).await
+    }
+
+    async fn digest_with_context<C: ContextProvider>(spec: &DigestModelSpec, memory: &DigestModelMemory, preferences: &DigestPreferences, input_items: &[InputItem]) -> DigestOutput {
+        Self::digest_with_citation_policy::<C>(spec, memory, preferences, input_items, InvalidCitationPolicy::Strip).await.unwrap()
     }
 
     async fn reflect(spec: &DigestModelSpec, memory: &DigestModelMemory, preferences: &DigestPreferences, input_items: &[InputItem], self_output: &DigestOutput, opponent_output: &DigestOutput, win: bool) -> DigestModelMemory {
@@ -80,3 +213,170 @@ impl DigestModel for BaselineDigestModel {
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_spec() -> DigestModelSpec {
+        DigestModelSpec {
+            uri: "tag:summarena.pages.dev,2025-08:digest_model/baseline".to_owned(),
+        }
+    }
+
+    fn test_memory() -> DigestModelMemory {
+        DigestModelMemory {
+            text: "".to_owned(),
+        }
+    }
+
+    fn test_preferences() -> DigestPreferences {
+        DigestPreferences {
+            uri: "tag:summarena.pages.dev,2025-08:digest_preferences/empty".to_owned(),
+            description: "".to_owned(),
+        }
+    }
+
+    fn test_input_item(name: &str, text: &str) -> InputItem {
+        InputItem {
+            uri: format!("tag:summarena.pages.dev,2025-08:input_item/test/{}", name),
+            live_source_uri: "tag:summarena.pages.dev,2025-08:live_source/test/baseline".to_owned(),
+            text: text.to_owned(),
+            vision: None,
+        }
+    }
+
+    /// Relates every item to three items of its own.
+    struct FanOutContextProvider;
+
+    impl ContextProvider for FanOutContextProvider {
+        async fn related_items(item: &InputItem, limit: usize) -> Vec<InputItem> {
+            (0..3).map(|n| InputItem { uri: format!("{}/related/{}", item.uri, n), ..test_input_item("", "Related") }).take(limit).collect()
+        }
+    }
+
+    /// Relates every item to the first test item and to one shared item.
+    struct OverlappingContextProvider;
+
+    impl ContextProvider for OverlappingContextProvider {
+        async fn related_items(item: &InputItem, limit: usize) -> Vec<InputItem> {
+            _ = item;
+            vec![test_input_item("0", "Zero"), test_input_item("shared", "Shared")].into_iter().take(limit).collect()
+        }
+    }
+
+    /// Relates every item to one item with no title and one with a title.
+    struct UntitledContextProvider;
+
+    impl ContextProvider for UntitledContextProvider {
+        async fn related_items(item: &InputItem, limit: usize) -> Vec<InputItem> {
+            _ = item;
+            vec![test_input_item("untitled", "\nNo title."), test_input_item("titled", "Titled")].into_iter().take(limit).collect()
+        }
+    }
+
+    fn pondered_preferences() -> PonderedPreferences {
+        PonderedPreferences {
+            look_out_for: "".to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn gather_context_respects_caps() {
+        let input_items = (0..10).map(|n| test_input_item(&n.to_string(), "Item")).collect::<Vec<InputItem>>();
+        let selected_input_items = input_items.iter().collect::<Vec<&InputItem>>();
+        let context_items = gather_context::<FanOutContextProvider>(&test_spec(), &pondered_preferences(), &selected_input_items).await;
+        let gathered = context_items.iter().map(|context_item| (context_item.supports, context_item.input_item.uri.clone())).collect::<Vec<(usize, String)>>();
+        let expected = (0..MAX_CONTEXT_ITEMS).map(|n| {
+            let supports = n / MAX_CONTEXT_ITEMS_PER_SELECTION;
+            (supports, format!("{}/related/{}", input_items[supports].uri, n % MAX_CONTEXT_ITEMS_PER_SELECTION))
+        }).collect::<Vec<(usize, String)>>();
+        assert_eq!(gathered, expected);
+    }
+
+    #[tokio::test]
+    async fn gather_context_dedupes_uris() {
+        let input_items = (0..3).map(|n| test_input_item(&n.to_string(), "Item")).collect::<Vec<InputItem>>();
+        let selected_input_items = input_items.iter().collect::<Vec<&InputItem>>();
+        let context_items = gather_context::<OverlappingContextProvider>(&test_spec(), &pondered_preferences(), &selected_input_items).await;
+        // Item 0 is already selected, and the shared item is only taken once.
+        let gathered = context_items.iter().map(|context_item| (context_item.supports, context_item.input_item.uri.clone())).collect::<Vec<(usize, String)>>();
+        assert_eq!(gathered, vec![(0, test_input_item("shared", "").uri)]);
+    }
+
+    #[tokio::test]
+    async fn composed_citations_round_trip() {
+        let input_items = vec![
+            test_input_item("rust", "Big news\nRust ships 2.0 today! More on that later."),
+            test_input_item("hello", "Hello, world!"),
+        ];
+        let output = BaselineDigestModel::digest(&test_spec(), &test_memory(), &test_preferences(), &input_items).await;
+        assert_eq!(output.text, "Big news [1a]\nRust ships 2.0 today! [1b] More on that later.\nHello, world! [2a]");
+
+        let resolved = output.resolve_citations().unwrap();
+        let cited = resolved.iter().map(|(citation, reference)| {
+            let input_item = &input_items[citation.selected_item_index];
+            (citation.marker().unwrap(), &input_item.text[reference.text_start_index..reference.text_end_index])
+        }).collect::<Vec<(String, &str)>>();
+        assert_eq!(cited, vec![
+            ("[1a]".to_owned(), "Big news"),
+            ("[1b]".to_owned(), "Rust ships 2.0 today!"),
+            ("[2a]".to_owned(), "Hello, world!"),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn citation_like_item_text_is_escaped() {
+        let input_items = vec![test_input_item("footnote", "Big news\nSee [1b] here. Also [2a].")];
+        let output = BaselineDigestModel::digest_with_citation_policy::<EmptyContextProvider>(&test_spec(), &test_memory(), &test_preferences(), &input_items, InvalidCitationPolicy::Fail).await.unwrap();
+        assert_eq!(output.text, "Big news [1a]\nSee \\[1b] here. [1b] Also \\[2a].");
+
+        let resolved = output.resolve_citations().unwrap();
+        let cited = resolved.iter().map(|(citation, reference)| (citation.marker().unwrap(), &input_items[0].text[reference.text_start_index..reference.text_end_index])).collect::<Vec<(String, &str)>>();
+        assert_eq!(cited, vec![("[1a]".to_owned(), "Big news"), ("[1b]".to_owned(), "See [1b] here.")]);
+    }
+
+    #[tokio::test]
+    async fn empty_spans_are_not_cited() {
+        let input_items = vec![
+            test_input_item("empty", ""),
+            test_input_item("blank_title", "\nBody text."),
+        ];
+        let output = BaselineDigestModel::digest_with_citation_policy::<EmptyContextProvider>(&test_spec(), &test_memory(), &test_preferences(), &input_items, InvalidCitationPolicy::Fail).await.unwrap();
+        assert_eq!(output.text, "\n\nBody text. [2b]");
+        assert!(output.selected_items[0].references.is_empty());
+        let resolved = output.resolve_citations().unwrap();
+        let cited = resolved.iter().map(|(citation, reference)| (citation.marker().unwrap(), &input_items[1].text[reference.text_start_index..reference.text_end_index])).collect::<Vec<(String, &str)>>();
+        assert_eq!(cited, vec![("[2b]".to_owned(), "Body text.")]);
+    }
+
+    #[tokio::test]
+    async fn gather_context_skips_untitled_items() {
+        let input_item = test_input_item("0", "Item");
+        let context_items = gather_context::<UntitledContextProvider>(&test_spec(), &pondered_preferences(), &[&input_item]).await;
+        let gathered = context_items.iter().map(|context_item| context_item.input_item.uri.clone()).collect::<Vec<String>>();
+        assert_eq!(gathered, vec![test_input_item("titled", "").uri]);
+    }
+
+    #[tokio::test]
+    async fn unwritable_citations_follow_policy() {
+        let summary = FocusedSummary {
+            summary_text: "Title\nBody.".to_owned(),
+            references: vec![
+                InputItemReference { text_start_index: 0, text_end_index: 5, label: Some("Title".to_owned()) },
+                InputItemReference { text_start_index: 6, text_end_index: 11, label: Some("b".to_owned()) },
+                InputItemReference { text_start_index: 6, text_end_index: 6, label: Some("c".to_owned()) },
+            ],
+        };
+        let pondered_preferences = pondered_preferences();
+
+        let stripped = compose_digest(&test_spec(), &pondered_preferences, std::slice::from_ref(&summary), &[], InvalidCitationPolicy::Strip).await;
+        assert_eq!(stripped, Ok("Title\nBody. [1b]".to_owned()));
+
+        let failed = compose_digest(&test_spec(), &pondered_preferences, &[summary], &[], InvalidCitationPolicy::Fail).await;
+        assert_eq!(failed, Err(vec![
+            DigestCitation { selected_item_index: 0, label: "Title".to_owned() },
+            DigestCitation { selected_item_index: 0, label: "c".to_owned() },
+        ]));
+    }
+}
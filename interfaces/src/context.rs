@@ -0,0 +1,65 @@
+use crate::defs::ContextProvider;
+use crate::defs::InputItem;
+use crate::state;
+
+/// How far back to look for related items.
+const WINDOW_DAYS: u32 = 7;
+
+/// Treats recent items from the same live source as related. A stand-in until
+/// there's a search or entity index to find same-thread items with.
+pub struct LiveSourceContextProvider;
+
+impl ContextProvider for LiveSourceContextProvider {
+    async fn related_items(item: &InputItem, limit: usize) -> Vec<InputItem> {
+        state::get_live_source_neighbors(item, WINDOW_DAYS, limit).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::baseline::BaselineDigestModel;
+    use crate::defs::DigestModel;
+    use crate::defs::DigestModelMemory;
+    use crate::defs::DigestModelSpec;
+    use crate::defs::DigestPreferences;
+    use crate::defs::LiveSourceSpec;
+
+    #[tokio::test]
+    async fn digest_pulls_earlier_part_of_series() {
+        state::migrate().await;
+        let live_source_spec = LiveSourceSpec {
+            uri: "tag:summarena.pages.dev,2025-08:live_source/test/series".to_owned(),
+        };
+        state::create_live_source_spec(&live_source_spec).await;
+        let part = |n: usize, text: &str| InputItem {
+            uri: format!("tag:summarena.pages.dev,2025-08:input_item/test/series/part{}", n),
+            live_source_uri: live_source_spec.uri.clone(),
+            text: text.to_owned(),
+            vision: None,
+        };
+        let part1 = part(1, "Series, part 1\nIt begins.");
+        let part2 = part(2, "Series, part 2\nIt continues.");
+        state::ingest(&part1).await;
+        state::ingest(&part2).await;
+
+        let spec = DigestModelSpec {
+            uri: "tag:summarena.pages.dev,2025-08:digest_model/baseline".to_owned(),
+        };
+        let memory = DigestModelMemory {
+            text: "".to_owned(),
+        };
+        let preferences = DigestPreferences {
+            uri: "tag:summarena.pages.dev,2025-08:digest_preferences/empty".to_owned(),
+            description: "".to_owned(),
+        };
+        let output = BaselineDigestModel::digest_with_context::<LiveSourceContextProvider>(&spec, &memory, &preferences, std::slice::from_ref(&part2)).await;
+
+        let selected = output.selected_items.iter().map(|selected_item| (selected_item.input_item_uri.as_str(), selected_item.context_for.as_deref())).collect::<Vec<(&str, Option<&str>)>>();
+        assert_eq!(selected, vec![(part2.uri.as_str(), None), (part1.uri.as_str(), Some(part2.uri.as_str()))]);
+        assert_eq!(output.text, "Series, part 2 [1a]\nIt continues. [1b]\nRelated: Series, part 1 [2a]");
+        let resolved = output.resolve_citations().unwrap();
+        let secondary = resolved.iter().find(|(citation, _)| citation.selected_item_index == 1).unwrap().1;
+        assert_eq!(&part1.text[secondary.text_start_index..secondary.text_end_index], "Series, part 1");
+    }
+}
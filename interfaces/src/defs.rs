@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 pub struct LiveSourceSpec {
     pub uri: String,
 }
@@ -14,6 +16,9 @@ pub struct InputItemReference {
     // tbd
     pub text_start_index: usize,
     pub text_end_index: usize,
+    /// Short identifier for the claim this span supports, e.g. `a`. Digest
+    /// text cites it as `[1a]`, so only lowercase ASCII letters are citable.
+    pub label: Option<String>,
 }
 
 pub struct WatchRest {
@@ -54,6 +59,9 @@ pub struct DigestModelMemory {
 pub struct DigestSelectedItem {
     pub input_item_uri: String,
     pub references: Vec<InputItemReference>,
+    /// `None` for a primary selection. For an item pulled in as context via
+    /// [`ContextProvider`], the URI of the selected item it supports.
+    pub context_for: Option<String>,
 }
 
 #[derive(Debug)]
@@ -62,6 +70,105 @@ pub struct DigestOutput {
     pub text: String,
 }
 
+/// A `[1a]`-style citation in [`DigestOutput::text`]: the 1-based position
+/// of a selected item followed by the label of one of its references.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DigestCitation {
+    pub selected_item_index: usize,
+    pub label: String,
+}
+
+impl DigestCitation {
+    /// The citation as written in digest text, or `None` if the label can't
+    /// be written that way.
+    pub fn marker(&self) -> Option<String> {
+        if self.label.is_empty() || !self.label.bytes().all(|byte| byte.is_ascii_lowercase()) {
+            return None;
+        }
+        Some(format!("[{}{}]", self.selected_item_index + 1, self.label))
+    }
+}
+
+/// What digest composition does with a citation it can't emit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvalidCitationPolicy {
+    /// Fail the composition, reporting the offending citations.
+    Fail,
+    /// Leave the citation out of the text.
+    Strip,
+}
+
+/// Find citations in `text`, with the byte range of each. A citation escaped
+/// as `\[1a]` is plain text, not a citation.
+fn find_citations(text: &str) -> Vec<(std::ops::Range<usize>, DigestCitation)> {
+    let bytes = text.as_bytes();
+    let mut citations = vec![];
+    let mut search_start = 0;
+    while let Some(offset) = text[search_start..].find('[') {
+        let open = search_start + offset;
+        let digits_end = open + 1 + bytes[open + 1..].iter().take_while(|byte| byte.is_ascii_digit()).count();
+        let label_end = digits_end + bytes[digits_end..].iter().take_while(|byte| byte.is_ascii_lowercase()).count();
+        search_start = open + 1;
+        if text[..open].ends_with('\\') || label_end == digits_end || bytes.get(label_end) != Some(&b']') {
+            continue;
+        }
+        let Some(number) = text[open + 1..digits_end].parse::<usize>().ok().filter(|number| *number > 0) else {
+            continue;
+        };
+        citations.push((open..label_end + 1, DigestCitation {
+            selected_item_index: number - 1,
+            label: text[digits_end..label_end].to_owned(),
+        }));
+        search_start = label_end + 1;
+    }
+    citations
+}
+
+/// Escape anything in `text` that would read as a citation, for copying text
+/// that isn't making claims (e.g. from an input item) into digest text.
+pub fn escape_citations(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut copied_up_to = 0;
+    for (range, _) in find_citations(text) {
+        escaped.push_str(&text[copied_up_to..range.start]);
+        escaped.push('\\');
+        copied_up_to = range.start;
+    }
+    escaped.push_str(&text[copied_up_to..]);
+    escaped
+}
+
+impl DigestOutput {
+    fn citation_reference(&self, citation: &DigestCitation) -> Option<&InputItemReference> {
+        self.selected_items.get(citation.selected_item_index)?
+            .references.iter()
+            .find(|reference| reference.label.as_deref() == Some(citation.label.as_str()))
+    }
+
+    /// Map each citation in the text to the span it points at.
+    ///
+    /// Fails closed: if any citation names a missing item or label, returns
+    /// all such citations instead. Escaped citations (see [`escape_citations`])
+    /// are skipped.
+    pub fn resolve_citations(&self) -> Result<BTreeMap<DigestCitation, &InputItemReference>, Vec<DigestCitation>> {
+        let mut resolved = BTreeMap::new();
+        let mut invalid = vec![];
+        for (_, citation) in find_citations(&self.text) {
+            match self.citation_reference(&citation) {
+                Some(reference) => {
+                    resolved.insert(citation, reference);
+                }
+                None => invalid.push(citation),
+            }
+        }
+        if invalid.is_empty() {
+            Ok(resolved)
+        } else {
+            Err(invalid)
+        }
+    }
+}
+
 // Object style note:
 // Envision the implementations of these traits (Ingester, DigestModel, etc.)
 // as written to run inside short lived single-task processes.
@@ -70,8 +177,23 @@ pub struct DigestOutput {
 // and load information in a database based on the spec parameter within each
 // method.
 
+pub trait ContextProvider {
+    /// Find items related to `item` that aren't necessarily in the digest's
+    /// input, e.g. an earlier part of a series or the article a follow-up
+    /// refers to.
+    ///
+    /// Return at most `limit` items, most relevant first, never including
+    /// `item` itself.
+    fn related_items(item: &InputItem, limit: usize) -> impl Future<Output = Vec<InputItem>>;
+}
+
 pub trait DigestModel {
     fn digest(spec: &DigestModelSpec, memory: &DigestModelMemory, preferences: &DigestPreferences, input_items: &[InputItem]) -> impl Future<Output = DigestOutput>;
+    /// Like [`Self::digest`], but able to look up items related to the input
+    /// through `C`. Models that don't use context can leave this as is.
+    fn digest_with_context<C: ContextProvider>(spec: &DigestModelSpec, memory: &DigestModelMemory, preferences: &DigestPreferences, input_items: &[InputItem]) -> impl Future<Output = DigestOutput> {
+        Self::digest(spec, memory, preferences, input_items)
+    }
     fn reflect(spec: &DigestModelSpec, memory: &DigestModelMemory, preferences: &DigestPreferences, input_items: &[InputItem], self_output: &DigestOutput, opponent_output: &DigestOutput, win: bool) -> impl Future<Output = DigestModelMemory>;
 }
 
@@ -81,3 +203,59 @@ pub struct DigestAttempt {
     pub model_uri: String,
     pub output: DigestOutput,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selected_item(labels: &[&str]) -> DigestSelectedItem {
+        DigestSelectedItem {
+            input_item_uri: "tag:summarena.pages.dev,2025-08:input_item/test/defs".to_owned(),
+            references: labels.iter().enumerate().map(|(index, label)| InputItemReference {
+                text_start_index: index,
+                text_end_index: index + 1,
+                label: Some(label.to_string()),
+            }).collect(),
+            context_for: None,
+        }
+    }
+
+    #[test]
+    fn resolve_citations_maps_markers_to_references() {
+        let output = DigestOutput {
+            selected_items: vec![selected_item(&["a", "b"]), selected_item(&["a"])],
+            text: "One [1a], one again [1b] [1a]. Two [2a]. Not citations: [0a] [b] [12] [1A].".to_owned(),
+        };
+        let resolved = output.resolve_citations().unwrap();
+        let spans = resolved.iter().map(|(citation, reference)| (citation.marker().unwrap(), reference.text_start_index)).collect::<Vec<(String, usize)>>();
+        assert_eq!(spans, vec![("[1a]".to_owned(), 0), ("[1b]".to_owned(), 1), ("[2a]".to_owned(), 0)]);
+    }
+
+    #[test]
+    fn resolve_citations_fails_closed() {
+        let output = DigestOutput {
+            selected_items: vec![selected_item(&["a"])],
+            text: "Fine [1a], missing label [1z], missing item [2a].".to_owned(),
+        };
+        assert_eq!(output.resolve_citations().unwrap_err(), vec![
+            DigestCitation { selected_item_index: 0, label: "z".to_owned() },
+            DigestCitation { selected_item_index: 1, label: "a".to_owned() },
+        ]);
+    }
+
+    #[test]
+    fn escaped_citations_are_not_parsed() {
+        let escaped = escape_citations("See [1b] and [[2a]; not [b] or \\[3c].");
+        assert_eq!(escaped, "See \\[1b] and [\\[2a]; not [b] or \\[3c].");
+        assert!(find_citations(&escaped).is_empty());
+    }
+
+    #[test]
+    fn marker_rejects_unwritable_labels() {
+        let citation = |label: &str| DigestCitation { selected_item_index: 2, label: label.to_owned() };
+        assert_eq!(citation("ab").marker().as_deref(), Some("[3ab]"));
+        assert_eq!(citation("").marker(), None);
+        assert_eq!(citation("Title").marker(), None);
+        assert_eq!(citation("a1").marker(), None);
+    }
+}
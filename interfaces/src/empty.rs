@@ -1,3 +1,4 @@
+use crate::defs::ContextProvider;
 use crate::defs::DigestModel;
 use crate::defs::DigestModelMemory;
 use crate::defs::DigestModelSpec;
@@ -33,3 +34,14 @@ impl DigestModel for EmptyDigestModel {
         }
     }
 }
+
+pub struct EmptyContextProvider;
+
+impl ContextProvider for EmptyContextProvider {
+    async fn related_items(item: &InputItem, limit: usize) -> Vec<InputItem> {
+        _ = item;
+        _ = limit;
+        // Nothing is related to anything.
+        vec![]
+    }
+}
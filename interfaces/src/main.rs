@@ -1,4 +1,5 @@
 use crate::baseline::BaselineDigestModel;
+use crate::context::LiveSourceContextProvider;
 use crate::defs::DigestModel;
 use crate::defs::DigestModelSpec;
 use crate::defs::DigestModelMemory;
@@ -10,6 +11,7 @@ use crate::defs::InputItemReference;
 use crate::defs::LiveSourceSpec;
 
 pub mod baseline;
+pub mod context;
 pub mod defs;
 pub mod empty;
 pub mod state;
@@ -47,7 +49,7 @@ async fn main() {
         uri: "tag:summarena.pages.dev,2025-08:digest_preferences/empty".to_owned(),
         description: "".to_owned(),
     };
-    let output = BaselineDigestModel::digest(&spec, &memory_in, &preferences, &input_items).await;
+    let output = BaselineDigestModel::digest_with_context::<LiveSourceContextProvider>(&spec, &memory_in, &preferences, &input_items).await;
     println!("output: {:#?}", &output);
     let other_output = DigestOutput {
         selected_items: vec![
@@ -57,8 +59,10 @@ async fn main() {
                     InputItemReference {
                         text_start_index: 0,
                         text_end_index: item0_text.len(),
+                        label: None,
                     },
                 ],
+                context_for: None,
             },
         ],
         text: "They said the usual hello world".to_owned(),
@@ -1,22 +1,63 @@
-use sqlx::Connection;
-use sqlx::SqliteConnection;
+use std::str::FromStr;
+
+use sqlx::SqlitePool;
 use sqlx::migrate::MigrateDatabase;
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::sqlite::SqlitePoolOptions;
+use tokio::sync::OnceCell;
 
 use crate::defs::InputItem;
 use crate::defs::LiveSourceSpec;
 
-async fn get_db_connection() -> SqliteConnection {
-    SqliteConnection::connect("sqlite:omni.db").await.unwrap()
+#[cfg(not(any(test, feature = "test-doubles")))]
+const DATABASE_URL: &str = "sqlite:omni.db";
+
+// Under test and with test-doubles, state lives in an in-memory database so
+// nothing touches the on-disk omni.db. This is intentionally simpler than the
+// real thing:
+// - Everything in the process shares the one database, so tests aren't
+//   isolated from each other and should stick to their own uris.
+// - Nothing survives the process exiting.
+#[cfg(any(test, feature = "test-doubles"))]
+const DATABASE_URL: &str = "sqlite::memory:";
+
+struct Db {
+    pool: SqlitePool,
+    // An in-memory database goes away with its last connection, and a pool
+    // connection can be closed under us, e.g. when the tokio runtime that last
+    // used it shuts down between tests. Hold one open for the whole process.
+    #[cfg(any(test, feature = "test-doubles"))]
+    _keep_alive: std::sync::Mutex<sqlx::SqliteConnection>,
+}
+
+static DB: OnceCell<Db> = OnceCell::const_new();
+
+async fn get_db_pool() -> &'static SqlitePool {
+    &DB.get_or_init(|| async {
+        let connect_options = SqliteConnectOptions::from_str(DATABASE_URL).unwrap();
+        let pool_options = SqlitePoolOptions::new();
+        // Connections to an in-memory database share sqlite's cache, where
+        // concurrent writers fail with SQLITE_LOCKED rather than waiting.
+        #[cfg(any(test, feature = "test-doubles"))]
+        let pool_options = pool_options.max_connections(1);
+        Db {
+            #[cfg(any(test, feature = "test-doubles"))]
+            _keep_alive: std::sync::Mutex::new(sqlx::ConnectOptions::connect(&connect_options).await.unwrap()),
+            pool: pool_options.connect_with(connect_options).await.unwrap(),
+        }
+    }).await.pool
 }
 
+/// Apply migrations. Safe to call repeatedly; already-applied migrations are
+/// skipped.
 pub async fn migrate() {
-    sqlx::Sqlite::create_database("sqlite:omni.db").await.unwrap();
-    let mut conn = get_db_connection().await;
-    sqlx::migrate!("./migrations").run(&mut conn).await.unwrap();
+    sqlx::Sqlite::create_database(DATABASE_URL).await.unwrap();
+    let pool = get_db_pool().await;
+    sqlx::migrate!("./migrations").run(pool).await.unwrap();
 }
 
 pub async fn create_live_source_spec(live_source_spec: &LiveSourceSpec) {
-    let mut conn = get_db_connection().await;
+    let pool = get_db_pool().await;
     sqlx::query("
         INSERT OR IGNORE INTO live_source_specs
             (uri)
@@ -24,24 +65,252 @@ pub async fn create_live_source_spec(live_source_spec: &LiveSourceSpec) {
             (?1)
     ")
     .bind(&live_source_spec.uri)
-    .execute(&mut conn)
+    .execute(pool)
     .await
     .unwrap();
 }
 
+/// Store an input item. An item whose uri was already ingested is left as is,
+/// even if its contents differ. A `None` vision is stored as NULL.
 pub async fn ingest(input_item: &InputItem) {
-    let mut conn = get_db_connection().await;
+    let pool = get_db_pool().await;
     sqlx::query("
         INSERT OR IGNORE INTO input_items
-            (uri, live_source_uri, text, vision)
+            (uri, live_source_uri, text, vision, ingested_at)
         VALUES
-            (?1, ?2, ?3, ?4)
+            (?1, ?2, ?3, ?4, CURRENT_TIMESTAMP)
     ")
     .bind(&input_item.uri)
     .bind(&input_item.live_source_uri)
     .bind(&input_item.text)
     .bind(&input_item.vision)
-    .execute(&mut conn)
+    .execute(pool)
     .await
     .unwrap();
 }
+
+/// Items from the same live source as `input_item` ingested within the last
+/// `window_days`, most recent first.
+pub async fn get_live_source_neighbors(input_item: &InputItem, window_days: u32, limit: usize) -> Vec<InputItem> {
+    let pool = get_db_pool().await;
+    let rows: Vec<(String, String, String, Option<Vec<u8>>)> = sqlx::query_as("
+        SELECT
+            uri, live_source_uri, text, vision
+        FROM input_items
+        WHERE live_source_uri = ?1
+            AND uri != ?2
+            AND ingested_at >= datetime('now', ?3)
+        ORDER BY ingested_at DESC, uri
+        LIMIT ?4
+    ")
+    .bind(&input_item.live_source_uri)
+    .bind(&input_item.uri)
+    .bind(format!("-{} days", window_days))
+    .bind(limit as i64)
+    .fetch_all(pool)
+    .await
+    .unwrap();
+    rows.into_iter().map(|(uri, live_source_uri, text, vision)| InputItem { uri, live_source_uri, text, vision }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct ExpectedColumn {
+        name: &'static str,
+        type_name: &'static str,
+        not_null: bool,
+        primary_key: bool,
+    }
+
+    /// The tables and columns the queries in this module rely on.
+    const EXPECTED_TABLES: &[(&str, &[ExpectedColumn])] = &[
+        ("live_source_specs", &[
+            ExpectedColumn { name: "uri", type_name: "TEXT", not_null: false, primary_key: true },
+        ]),
+        ("input_items", &[
+            ExpectedColumn { name: "uri", type_name: "TEXT", not_null: false, primary_key: true },
+            ExpectedColumn { name: "live_source_uri", type_name: "TEXT", not_null: true, primary_key: false },
+            ExpectedColumn { name: "text", type_name: "TEXT", not_null: true, primary_key: false },
+            ExpectedColumn { name: "vision", type_name: "BLOB", not_null: false, primary_key: false },
+            ExpectedColumn { name: "ingested_at", type_name: "TIMESTAMP", not_null: false, primary_key: false },
+        ]),
+    ];
+
+    struct ExpectedIndex {
+        table: &'static str,
+        columns: &'static str,
+        unique: bool,
+    }
+
+    /// The indexes the queries in this module rely on: `uri` must be uniquely
+    /// indexed for the INSERT OR IGNOREs to dedupe, and the live-source neighbor
+    /// lookup searches by source and ingestion time.
+    const EXPECTED_INDEXES: &[ExpectedIndex] = &[
+        ExpectedIndex { table: "live_source_specs", columns: "uri", unique: true },
+        ExpectedIndex { table: "input_items", columns: "uri", unique: true },
+        ExpectedIndex { table: "input_items", columns: "live_source_uri,ingested_at", unique: false },
+    ];
+
+    /// Compare the migrated schema against [`EXPECTED_TABLES`] and
+    /// [`EXPECTED_INDEXES`], returning a
+    /// description of each difference. Empty when everything matches.
+    async fn verify_schema() -> Vec<String> {
+        let pool = get_db_pool().await;
+        let mut mismatches = vec![];
+        for (table, expected_columns) in EXPECTED_TABLES {
+            let columns: Vec<(String, String, bool, i64)> = sqlx::query_as("
+                SELECT
+                    name, type, \"notnull\", pk
+                FROM pragma_table_info(?1)
+                ORDER BY cid
+            ")
+            .bind(table)
+            .fetch_all(pool)
+            .await
+            .unwrap();
+            if columns.is_empty() {
+                mismatches.push(format!("{}: table missing", table));
+                continue;
+            }
+            for expected in *expected_columns {
+                let Some((_, type_name, not_null, pk)) = columns.iter().find(|(name, ..)| name == expected.name) else {
+                    mismatches.push(format!("{}.{}: column missing", table, expected.name));
+                    continue;
+                };
+                if !type_name.eq_ignore_ascii_case(expected.type_name) {
+                    mismatches.push(format!("{}.{}: expected type {}, found {}", table, expected.name, expected.type_name, type_name));
+                }
+                if *not_null != expected.not_null {
+                    mismatches.push(format!("{}.{}: expected not_null {}, found {}", table, expected.name, expected.not_null, not_null));
+                }
+                if (*pk != 0) != expected.primary_key {
+                    mismatches.push(format!("{}.{}: expected primary_key {}, found {}", table, expected.name, expected.primary_key, *pk != 0));
+                }
+            }
+            for (name, ..) in &columns {
+                if !expected_columns.iter().any(|expected| expected.name == name) {
+                    mismatches.push(format!("{}.{}: unexpected column", table, name));
+                }
+            }
+        }
+        for expected in EXPECTED_INDEXES {
+            let indexes: Vec<(bool, Option<String>)> = sqlx::query_as("
+                SELECT
+                    index_list.\"unique\",
+                    (SELECT group_concat(name) FROM (
+                        SELECT name FROM pragma_index_info(index_list.name) ORDER BY seqno
+                    ))
+                FROM pragma_index_list(?1) AS index_list
+            ")
+            .bind(expected.table)
+            .fetch_all(pool)
+            .await
+            .unwrap();
+            let found = indexes.iter().any(|(unique, columns)| {
+                columns.as_deref() == Some(expected.columns) && (*unique || !expected.unique)
+            });
+            if !found {
+                let kind = if expected.unique { "unique index" } else { "index" };
+                mismatches.push(format!("{}({}): no {}", expected.table, expected.columns, kind));
+            }
+        }
+        mismatches
+    }
+
+    fn test_live_source_spec(name: &str) -> LiveSourceSpec {
+        LiveSourceSpec {
+            uri: format!("tag:summarena.pages.dev,2025-08:live_source/test/{}", name),
+        }
+    }
+
+    fn test_input_item(live_source_spec: &LiveSourceSpec, name: &str, text: &str) -> InputItem {
+        InputItem {
+            uri: format!("tag:summarena.pages.dev,2025-08:input_item/test/{}", name),
+            live_source_uri: live_source_spec.uri.clone(),
+            text: text.to_owned(),
+            vision: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn migrated_schema_matches_expectations() {
+        migrate().await;
+        assert_eq!(verify_schema().await, Vec::<String>::new());
+    }
+
+    #[tokio::test]
+    async fn migrate_is_idempotent() {
+        migrate().await;
+        migrate().await;
+        assert_eq!(verify_schema().await, Vec::<String>::new());
+        let (applied,): (i64,) = sqlx::query_as("SELECT count(*) FROM _sqlx_migrations")
+            .fetch_one(get_db_pool().await)
+            .await
+            .unwrap();
+        assert_eq!(applied as usize, sqlx::migrate!("./migrations").iter().count());
+    }
+
+    #[tokio::test]
+    async fn ingest_edge_cases() {
+        migrate().await;
+        let live_source_spec = test_live_source_spec("ingest_edge_cases");
+        create_live_source_spec(&live_source_spec).await;
+        let probe = test_input_item(&live_source_spec, "ingest_edge_cases/probe", "probe");
+        let no_vision = test_input_item(&live_source_spec, "ingest_edge_cases/no_vision", "first");
+        let long_text = "Grüße, world! ".repeat(100_000);
+        let long = test_input_item(&live_source_spec, "ingest_edge_cases/long", &long_text);
+        let duplicate = InputItem {
+            text: "second".to_owned(),
+            vision: Some(vec![1, 2, 3]),
+            ..test_input_item(&live_source_spec, "ingest_edge_cases/no_vision", "")
+        };
+        for input_item in [&probe, &no_vision, &long, &duplicate] {
+            ingest(input_item).await;
+        }
+
+        let (vision_is_null,): (bool,) = sqlx::query_as("SELECT vision IS NULL FROM input_items WHERE uri = ?1")
+            .bind(&no_vision.uri)
+            .fetch_one(get_db_pool().await)
+            .await
+            .unwrap();
+        assert!(vision_is_null);
+
+        let neighbors = get_live_source_neighbors(&probe, 1, 10).await;
+        assert_eq!(neighbors.len(), 2);
+        let stored_no_vision = neighbors.iter().find(|input_item| input_item.uri == no_vision.uri).unwrap();
+        // The duplicate was ignored rather than overwriting the first ingest.
+        assert_eq!(stored_no_vision.text, "first");
+        assert_eq!(stored_no_vision.vision, None);
+        let stored_long = neighbors.iter().find(|input_item| input_item.uri == long.uri).unwrap();
+        assert_eq!(stored_long.text, long_text);
+    }
+
+    #[cfg(feature = "test-doubles")]
+    #[tokio::test]
+    async fn test_doubles_stay_in_memory() {
+        let omni_db_existed = std::path::Path::new("omni.db").exists();
+        migrate().await;
+        let live_source_spec = LiveSourceSpec {
+            uri: "tag:summarena.pages.dev,2025-08:live_source/test/in_memory".to_owned(),
+        };
+        create_live_source_spec(&live_source_spec).await;
+        ingest(&InputItem {
+            uri: "tag:summarena.pages.dev,2025-08:input_item/test/in_memory".to_owned(),
+            live_source_uri: live_source_spec.uri.clone(),
+            text: "Hello, world!".to_owned(),
+            vision: None,
+        }).await;
+
+        // sqlite reports an empty file name for databases not backed by one.
+        let database_files: Vec<(String,)> = sqlx::query_as("SELECT file FROM pragma_database_list")
+            .fetch_all(get_db_pool().await)
+            .await
+            .unwrap();
+        assert_eq!(database_files, vec![("".to_owned(),)]);
+        if !omni_db_existed {
+            assert!(!std::path::Path::new("omni.db").exists());
+        }
+    }
+}